@@ -82,6 +82,21 @@ pub trait Chunk<DataType, const N: usize>: Sampleable<DataType, N> + Sized {
     /// }
     /// ```
     fn iter(&self) -> ChunkIterator<'_, Self, DataType, N>;
+
+    /// Encodes this `Chunk` into a standalone byte blob suitable for writing to
+    /// disk. The blob begins with an eight-byte header — the magic tag `BVTC`,
+    /// the dimensionality `N`, the width exponent, and the size of one
+    /// `DataType` — followed by the raw linear `data` array. The header lets a
+    /// loader validate that a file matches the chunk type it is being read into
+    /// before it touches the body. See [`deserialize`](Self::deserialize).
+    fn serialize(&self) -> Vec<u8>;
+
+    /// Reconstructs a `Chunk` from bytes produced by [`serialize`](Self::serialize).
+    /// The header is checked against this chunk type — dimensionality, width
+    /// exponent, and `DataType` size must all match, and the body must be
+    /// exactly `SIZE` elements long — so loading a file written for a different
+    /// chunk type panics rather than silently producing garbage.
+    fn deserialize(bytes: &[u8]) -> Self;
 }
 
 /// The `ChunkIterator` allows you to access every element of data in a `Chunk`
@@ -244,6 +259,38 @@ macro_rules! create_chunk_type {
                     chunk_type: PhantomData,
                 }
             }
+
+            fn serialize(&self) -> Vec<u8> {
+                const MAGIC: [u8; 4] = *b"BVTC";
+                let element_size = core::mem::size_of::<$data_type>();
+                let mut bytes = Vec::with_capacity(8 + Self::SIZE * element_size);
+                bytes.extend_from_slice(&MAGIC);
+                bytes.push($dim as u8);
+                bytes.push($exp as u8);
+                bytes.push(element_size as u8);
+                bytes.push(0); // reserved, keeps the header a round eight bytes
+                bytes.extend_from_slice(bytemuck::cast_slice(&self.data));
+                bytes
+            }
+
+            fn deserialize(bytes: &[u8]) -> Self {
+                const MAGIC: [u8; 4] = *b"BVTC";
+                let element_size = core::mem::size_of::<$data_type>();
+                assert!(bytes.len() >= 8, "chunk data is too short to contain a header");
+                assert_eq!(&bytes[0..4], &MAGIC, "not a bevy_terrain chunk blob");
+                assert_eq!(bytes[4] as usize, $dim, "chunk dimensionality mismatch");
+                assert_eq!(bytes[5] as usize, $exp, "chunk width exponent mismatch");
+                assert_eq!(bytes[6] as usize, element_size, "chunk data type size mismatch");
+                let body = &bytes[8..];
+                assert_eq!(
+                    body.len(),
+                    Self::SIZE * element_size,
+                    "chunk body length does not match the expected chunk size"
+                );
+                let mut chunk = Self::new();
+                bytemuck::cast_slice_mut(&mut chunk.data).copy_from_slice(body);
+                chunk
+            }
         }
     };
 }
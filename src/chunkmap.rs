@@ -1,41 +1,660 @@
-use bevy::{prelude::*, utils::HashMap};
+use std::path::{Path, PathBuf};
 
-use crate::chunk::Chunk3x16;
+use bevy::{
+    prelude::*,
+    utils::{HashMap, HashSet},
+};
+
+use crate::chunk::{Chunk, Chunk3x16, Sampleable};
 
 pub struct ChunkmapPlugin;
 
 impl Plugin for ChunkmapPlugin {
     fn build(&self, app: &mut App) {
-        app.add_systems(Update, drop_chunks);
+        app.add_systems(
+            Update,
+            (mark_chunks_from_agents, update_lod_from_agents, drop_chunks),
+        );
     }
 }
 
+/// Identifies a chunk slot in the map: its coordinate in chunk units together
+/// with the level of detail it is stored at. Level `0` is full resolution;
+/// each successive level halves the resolution and doubles the world extent a
+/// single chunk covers, so a coarse chunk at level `L` spans the region of
+/// `2^L` fine chunks per axis.
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ChunkKey {
+    pub coord: UVec3,
+    pub lod: u8,
+}
+
 #[derive(Component)]
 pub struct Chunkmap {
-    chunks: HashMap<UVec3, Chunk3x16>,
+    chunks: HashMap<ChunkKey, Chunk3x16>,
     chunk_scale: u8,
-    outdated_chunks: Vec<UVec3>,
+    outdated_chunks: Vec<ChunkKey>,
+    /// The LOD currently selected for each chunk coordinate, so we can detect
+    /// when an agent moving changes the required detail and re-mesh only the
+    /// chunks that actually transitioned.
+    lods: HashMap<UVec3, Lod>,
+    /// The coarsest level this map will ever generate; chunks beyond the range
+    /// of every agent are capped here.
+    max_lod: u8,
+    /// Chunk coordinates that have fallen outside every agent's load radius and
+    /// are waiting for `drop_chunks` to despawn their mesh and free their data.
+    dropped_chunks: Vec<UVec3>,
+    /// Freshly allocated chunks awaiting GPU generation, collected so the
+    /// `TerrainGenerator` can dispatch them asynchronously. A chunk stays empty
+    /// until its generated voxels are applied and it is re-queued for meshing.
+    to_generate: Vec<UVec3>,
+    /// The mesh entity spawned for each loaded chunk, so a dropped chunk can be
+    /// despawned again. Populated by the voxel mesher as it processes
+    /// `outdated_chunks`.
+    chunk_entities: HashMap<UVec3, Entity>,
+    /// Directory chunks are persisted to, keyed by coordinate. When set,
+    /// streaming a region in reads a saved chunk from disk instead of
+    /// allocating a fresh one. `None` keeps the map entirely in memory.
+    save_dir: Option<PathBuf>,
     channel: u8,
 }
 
 impl Chunkmap {
-    fn update_from_prev_pos(prev: UVec3, curr: UVec3) {}
+    /// Creates an empty map. Chunks stream in lazily as [`ChunkmapAgent`]s move
+    /// through it, so nothing is queued up front. The map defaults to a single
+    /// world-unit chunk scale, level-0-only detail, and channel `0`; the
+    /// `with_*` builder methods override these before the component is spawned.
+    pub fn new() -> Self {
+        Self {
+            chunks: HashMap::new(),
+            chunk_scale: 1,
+            outdated_chunks: vec![],
+            lods: HashMap::new(),
+            max_lod: 0,
+            dropped_chunks: vec![],
+            to_generate: vec![],
+            chunk_entities: HashMap::new(),
+            save_dir: None,
+            channel: 0,
+        }
+    }
+
+    /// Backs this map with a directory on disk. Chunks written by
+    /// [`save_chunks`](Self::save_chunks) land here, and chunks streaming into
+    /// range are reloaded from here when a matching file exists.
+    pub fn with_save_dir(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.save_dir = Some(dir.into());
+        self
+    }
+
+    /// Sets the coarsest level of detail this map will generate; chunks beyond
+    /// the range of every agent are capped at this level.
+    pub fn with_max_lod(mut self, max_lod: u8) -> Self {
+        self.max_lod = max_lod;
+        self
+    }
+
+    /// Sets how many voxel widths of agent movement one chunk spans: an
+    /// agent's translation is divided by `WIDTH * chunk_scale` to find its
+    /// chunk coordinate. The voxel mesher places and sizes chunk meshes in
+    /// voxel units and does not apply this scale.
+    pub fn with_chunk_scale(mut self, chunk_scale: u8) -> Self {
+        self.chunk_scale = chunk_scale;
+        self
+    }
+
+    /// Binds this map to a channel, so only [`ChunkmapAgent`]s sharing it drive
+    /// its streaming and level-of-detail selection.
+    pub fn with_channel(mut self, channel: u8) -> Self {
+        self.channel = channel;
+        self
+    }
+
+    /// Ensures the level-0 chunk at `coord` is loaded (reading it from disk or
+    /// allocating it fresh, queuing fresh allocations for GPU generation), and
+    /// returns its key.
+    fn ensure_loaded(&mut self, coord: UVec3) -> ChunkKey {
+        let key = ChunkKey { coord, lod: 0 };
+        if !self.chunks.contains_key(&key) {
+            let (chunk, from_disk) = self.load_or_create(coord);
+            self.chunks.insert(key, chunk);
+            // A freshly allocated (not loaded) chunk starts empty and is
+            // queued for asynchronous GPU generation.
+            if !from_disk {
+                self.to_generate.push(coord);
+            }
+        }
+        key
+    }
+
+    /// Loads every chunk within `radius` of `center` and queues it for
+    /// meshing. Used to give a freshly spawned [`ChunkmapAgent`] its initial
+    /// neighborhood, since streaming otherwise only runs off movement.
+    fn load_initial(&mut self, center: UVec3, radius: u32) {
+        for coord in chunks_within(center, radius) {
+            let key = self.ensure_loaded(coord);
+            self.outdated_chunks.push(key);
+        }
+    }
+
+    /// Reconciles the set of loaded chunks when an agent moves from `prev` to
+    /// `curr` (both in chunk units). Chunks that are within `radius` of the new
+    /// position but were not within `radius` of the old one are allocated
+    /// lazily and queued for meshing; chunks that were in range before but are
+    /// no longer, and are not within `other_agents`' own radius of their
+    /// current position (every agent sharing this map's channel, including
+    /// this one), are queued for disposal. Only the symmetric difference of
+    /// the two neighborhoods is touched, so a single-chunk step only ever
+    /// processes the thin shell that actually entered or left.
+    fn update_from_prev_pos(
+        &mut self,
+        prev: UVec3,
+        curr: UVec3,
+        radius: u32,
+        other_agents: &[(UVec3, u32)],
+    ) {
+        let old = chunks_within(prev, radius);
+        let new = chunks_within(curr, radius);
+
+        for coord in new.difference(&old) {
+            let key = self.ensure_loaded(*coord);
+            self.outdated_chunks.push(key);
+        }
+
+        for coord in old.difference(&new) {
+            // A chunk leaving this agent's radius may still be in range of
+            // another agent on the same channel; only drop it once nothing on
+            // the channel still wants it loaded.
+            let still_covered = other_agents
+                .iter()
+                .any(|(position, load_radius)| chebyshev(*position, *coord) <= *load_radius);
+            if !still_covered {
+                self.dropped_chunks.push(*coord);
+            }
+        }
+    }
+
+    /// Samples the map at a global voxel coordinate, transparently crossing
+    /// chunk boundaries, against the chunk grid for a specific `lod`. A chunk
+    /// at level `lod` covers `2^lod` fine voxels per axis per cell, so `pos`
+    /// (always given in fine-voxel coordinates) is scaled down by that stride
+    /// before it is floor-divided by `Chunk::WIDTH` to find which chunk owns
+    /// it and where inside that chunk the cell lives; if the owning chunk is
+    /// not currently loaded at that level (or lies in the negative, unkeyed
+    /// octant), an "ambient" default value is returned so the mesher can
+    /// safely read one cell past a chunk face. This is the 3D counterpart to
+    /// `PlanarTerrain::sample_world`, and is what greedy meshing relies on to
+    /// decide whether a face is exposed at a chunk border, always comparing
+    /// against a neighbor at the same level of detail.
+    pub fn sample_world(&self, pos: IVec3, lod: u8) -> u8 {
+        let stride = 1i32 << lod;
+        let span = (Chunk3x16::WIDTH as i32) * stride;
+        let chunk_coord = IVec3::new(
+            pos.x.div_euclid(span),
+            pos.y.div_euclid(span),
+            pos.z.div_euclid(span),
+        );
+        if chunk_coord.x < 0 || chunk_coord.y < 0 || chunk_coord.z < 0 {
+            return u8::default();
+        }
+        let key = ChunkKey {
+            coord: chunk_coord.as_uvec3(),
+            lod,
+        };
+        match self.chunks.get(&key) {
+            Some(chunk) => chunk.sample([
+                (pos.x.rem_euclid(span) / stride) as usize,
+                (pos.y.rem_euclid(span) / stride) as usize,
+                (pos.z.rem_euclid(span) / stride) as usize,
+            ]),
+            None => u8::default(),
+        }
+    }
+
+    /// Writes every loaded chunk to its own file under the save directory,
+    /// keyed by chunk coordinate. Does nothing when no save directory is set.
+    pub fn save_chunks(&self) -> std::io::Result<()> {
+        let Some(dir) = &self.save_dir else {
+            return Ok(());
+        };
+        std::fs::create_dir_all(dir)?;
+        for (key, chunk) in &self.chunks {
+            if key.lod == 0 {
+                std::fs::write(chunk_path(dir, key.coord), chunk.serialize())?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Loads the chunk at `coord` from the save directory, falling back to a
+    /// fresh empty chunk when no save directory is set or no file exists yet.
+    /// The returned `bool` reports whether a file was actually found, so the
+    /// caller can tell a chunk that genuinely needs GPU generation apart from
+    /// one that was just reloaded from disk.
+    fn load_or_create(&self, coord: UVec3) -> (Chunk3x16, bool) {
+        match self
+            .save_dir
+            .as_ref()
+            .and_then(|dir| std::fs::read(chunk_path(dir, coord)).ok())
+        {
+            Some(bytes) => (Chunk3x16::deserialize(&bytes), true),
+            None => (Chunk3x16::new(), false),
+        }
+    }
+
+    /// Drains and returns the chunks queued for (re)meshing. The voxel mesher
+    /// calls this once per frame and turns each key into a mesh.
+    pub(crate) fn take_outdated(&mut self) -> Vec<ChunkKey> {
+        std::mem::take(&mut self.outdated_chunks)
+    }
+
+    /// Drains the chunk coordinates awaiting GPU generation so the terrain
+    /// generator can dispatch them. Returns an empty vector when nothing is
+    /// pending or no generator is wired up.
+    pub(crate) fn take_to_generate(&mut self) -> Vec<UVec3> {
+        std::mem::take(&mut self.to_generate)
+    }
+
+    /// Writes a generated voxel array back into the level-0 chunk at `coord`
+    /// and re-queues it for meshing. Ignored when the chunk is no longer loaded
+    /// (e.g. it was streamed out before generation finished).
+    pub(crate) fn apply_generated(&mut self, coord: UVec3, voxels: &[u8]) {
+        let key = ChunkKey { coord, lod: 0 };
+        let Some(chunk) = self.chunks.get_mut(&key) else {
+            return;
+        };
+        for index in 0..Chunk3x16::SIZE {
+            chunk.write(Chunk3x16::delinearize(index), voxels[index]);
+        }
+        self.outdated_chunks.push(key);
+    }
+
+    /// Borrows the chunk data stored at `key`, if it is loaded. Used by the
+    /// mesher to read a chunk's voxels; neighbor voxels that fall outside it
+    /// are read through [`sample_world`](Self::sample_world) instead.
+    pub(crate) fn chunk(&self, key: ChunkKey) -> Option<&Chunk3x16> {
+        self.chunks.get(&key)
+    }
+
+    /// Records the mesh entity spawned for a chunk coordinate and returns the
+    /// entity that previously represented it, for the caller to despawn (so a
+    /// re-mesh replaces the old mesh rather than stacking a second one on top).
+    pub(crate) fn replace_chunk_entity(&mut self, coord: UVec3, entity: Entity) -> Option<Entity> {
+        self.chunk_entities.insert(coord, entity)
+    }
+
+    /// Returns the level of detail a chunk should be stored at given its
+    /// chebyshev `distance`, in chunk units, from the nearest agent. The
+    /// innermost ring stays at level `0`; every power-of-two further out steps
+    /// the level up by one, capped at `max_lod`.
+    fn desired_lod(&self, distance: u32) -> u8 {
+        // `floor(log2(distance))`, with distance `0` and `1` both at level 0.
+        let level = (u32::BITS - distance.max(1).leading_zeros() - 1) as u8;
+        level.min(self.max_lod)
+    }
+
+    /// Builds the chunk one level coarser than its eight children by striding.
+    /// Each child at level `L` contributes one octant of the coarse chunk at
+    /// level `L + 1`: its voxel `[2u, 2v, 2w]` becomes the coarse voxel
+    /// `[ox * HALF + u, oy * HALF + v, oz * HALF + w]`, where `(ox, oy, oz)` is
+    /// the octant the child occupies. A single corner sample (rather than
+    /// averaging the `2³` block) is taken so material ids survive downsampling
+    /// intact. Absent children leave their octant at the default value.
+    fn downsample_block(children: &[Option<&Chunk3x16>; 8]) -> Chunk3x16 {
+        const HALF: usize = Chunk3x16::WIDTH / 2;
+        let mut coarse = Chunk3x16::new();
+        for (octant, child) in children.iter().enumerate() {
+            let Some(child) = child else {
+                continue;
+            };
+            let (ox, oy, oz) = (octant & 1, (octant >> 1) & 1, (octant >> 2) & 1);
+            for u in 0..HALF {
+                for v in 0..HALF {
+                    for w in 0..HALF {
+                        let val = child.sample([u * 2, v * 2, w * 2]);
+                        coarse.write([ox * HALF + u, oy * HALF + v, oz * HALF + w], val);
+                    }
+                }
+            }
+        }
+        coarse
+    }
+
+    /// Ensures a chunk exists at `(coord, lod)`, synthesising coarser levels
+    /// from their finer children on demand. Level `0` is the streamed/loaded
+    /// data, so it is reported present or absent as-is; every higher level is
+    /// built once from the eight level-`lod - 1` children at `2 * coord` plus
+    /// `0` or `1` on each axis via [`downsample_block`](Self::downsample_block) and cached
+    /// under its key. Returns whether a chunk is now stored for the key.
+    fn ensure_lod(&mut self, coord: UVec3, lod: u8) -> bool {
+        let key = ChunkKey { coord, lod };
+        if self.chunks.contains_key(&key) {
+            return true;
+        }
+        if lod == 0 {
+            // Full-resolution data only ever comes from streaming or disk.
+            return false;
+        }
+
+        // Build every child one level finer before downsampling from them.
+        for octant in 0..8u32 {
+            let offset = UVec3::new(octant & 1, (octant >> 1) & 1, (octant >> 2) & 1);
+            self.ensure_lod(coord * 2 + offset, lod - 1);
+        }
+
+        // Borrow the children, downsample into a coarse chunk, then store it;
+        // the immutable borrow of `chunks` ends before the insert.
+        let coarse = {
+            let children: [Option<&Chunk3x16>; 8] = std::array::from_fn(|octant| {
+                let offset = UVec3::new(
+                    (octant & 1) as u32,
+                    ((octant >> 1) & 1) as u32,
+                    ((octant >> 2) & 1) as u32,
+                );
+                self.chunks.get(&ChunkKey {
+                    coord: coord * 2 + offset,
+                    lod: lod - 1,
+                })
+            });
+            Self::downsample_block(&children)
+        };
+        self.chunks.insert(key, coarse);
+        true
+    }
 }
 
+/// Tracks the level of detail for a single chunk as a `(current, desired)`
+/// pair. While a transition is pending the two differ, which lets a
+/// high-detail chunk bordering a low-detail neighbor be stitched before the
+/// current level is advanced to match the desired one.
 pub struct Lod(u8, u8);
 
+impl Lod {
+    /// The level this chunk is actually built and meshed at right now.
+    fn current(&self) -> u8 {
+        self.0
+    }
+
+    /// The level `update_lod_from_agents` wants this chunk to settle at. Only
+    /// differs from [`current`](Self::current) for one update while a
+    /// transition is pending.
+    fn desired(&self) -> u8 {
+        self.1
+    }
+}
+
 #[derive(Component)]
 pub struct ChunkmapAgent {
+    /// The chunk the agent occupied last frame, in chunk units. Compared
+    /// against the agent's current chunk to detect when streaming must run.
     previous_position: UVec3,
+    /// Whether this agent has had its initial neighborhood loaded yet. Until
+    /// then, `mark_chunks_from_agents` loads everything within `load_radius`
+    /// of the agent's current position rather than diffing against
+    /// `previous_position`, since nothing has streamed in for it at all yet.
+    initialized: bool,
+    /// How many chunks out from the agent, as a chebyshev radius, should stay
+    /// loaded around it.
+    load_radius: u32,
     channel: u8,
 }
 
+impl ChunkmapAgent {
+    /// Creates an agent starting at `position` (in chunk units). `position`
+    /// only fills `previous_position` until the agent's first update, which
+    /// loads the initial neighborhood around the chunk the entity's transform
+    /// is in and replaces `previous_position` with that chunk.
+    /// Defaults to a load radius of `2` chunks and channel `0`; the `with_*`
+    /// builder methods override these before the component is spawned.
+    pub fn new(position: UVec3) -> Self {
+        Self {
+            previous_position: position,
+            initialized: false,
+            load_radius: 2,
+            channel: 0,
+        }
+    }
+
+    /// Sets how many chunks out, as a chebyshev radius, should stay loaded
+    /// around this agent.
+    pub fn with_load_radius(mut self, load_radius: u32) -> Self {
+        self.load_radius = load_radius;
+        self
+    }
+
+    /// Binds this agent to a channel, so it only drives streaming and
+    /// level-of-detail selection on [`Chunkmap`]s sharing it.
+    pub fn with_channel(mut self, channel: u8) -> Self {
+        self.channel = channel;
+        self
+    }
+}
+
+/// Streams chunks in and out around every agent. A freshly spawned agent has
+/// its whole neighborhood loaded immediately; after that, when an agent's
+/// current chunk differs from the one it occupied last frame, the map it
+/// drives (matched by `channel`) reconciles its loaded set against the
+/// agent's new neighborhood — without dropping anything still covered by
+/// another agent on the same channel — and the agent's remembered position is
+/// advanced.
 fn mark_chunks_from_agents(
-    mut commands: Commands,
-    chunkmap_query: Query<&Chunkmap>,
-    agent_query: Query<&ChunkmapAgent>,
+    mut chunkmap_query: Query<&mut Chunkmap>,
+    mut agent_query: Query<(&mut ChunkmapAgent, &Transform)>,
+) {
+    for mut chunkmap in &mut chunkmap_query {
+        let width = (Chunk3x16::WIDTH as u32) * chunkmap.chunk_scale.max(1) as u32;
+
+        // Every agent driving this map, paired with its current chunk
+        // position and load radius, so a coordinate leaving one agent's
+        // radius can be checked against every other agent sharing this
+        // channel before it is dropped.
+        let positions: Vec<(UVec3, u32)> = agent_query
+            .iter()
+            .filter(|(agent, _)| agent.channel == chunkmap.channel)
+            .map(|(agent, transform)| {
+                (transform.translation.as_uvec3() / width, agent.load_radius)
+            })
+            .collect();
+
+        for (mut agent, transform) in &mut agent_query {
+            if agent.channel != chunkmap.channel {
+                continue;
+            }
+            let current = transform.translation.as_uvec3() / width;
+            if !agent.initialized {
+                chunkmap.load_initial(current, agent.load_radius);
+                agent.previous_position = current;
+                agent.initialized = true;
+                continue;
+            }
+            if current == agent.previous_position {
+                continue;
+            }
+            chunkmap.update_from_prev_pos(
+                agent.previous_position,
+                current,
+                agent.load_radius,
+                &positions,
+            );
+            agent.previous_position = current;
+        }
+    }
+}
+
+/// The set of chunk coordinates within `radius` (chebyshev) of `center`,
+/// clamped to the non-negative octant that the map keys on.
+fn chunks_within(center: UVec3, radius: u32) -> HashSet<UVec3> {
+    let mut set = HashSet::new();
+    let r = radius as i32;
+    let c = center.as_ivec3();
+    for x in -r..=r {
+        for y in -r..=r {
+            for z in -r..=r {
+                let p = c + IVec3::new(x, y, z);
+                if p.x >= 0 && p.y >= 0 && p.z >= 0 {
+                    set.insert(p.as_uvec3());
+                }
+            }
+        }
+    }
+    set
+}
+
+/// The farthest chebyshev distance (in fine chunk units) that
+/// `update_lod_from_agents` will ever assign a level of detail for, given a
+/// map's `max_lod`. `desired_lod` keeps returning `max_lod` for anything
+/// past this, so it is also the effective render distance of the LOD field:
+/// one ring past it, the level would be the same as at the ring before, so
+/// there is nothing more to stitch by reaching further.
+fn max_lod_extent(max_lod: u8) -> u32 {
+    (1u32 << (max_lod as u32 + 1)) - 1
+}
+
+/// Re-selects the level of detail of every chunk coordinate reachable by the
+/// agents driving its map — not just the ones already streamed in at full
+/// resolution, but every coordinate out to [`max_lod_extent`] so far chunks
+/// are assigned progressively coarser LODs instead of staying unassigned past
+/// the streaming radius. For each coordinate it takes the smallest chebyshev
+/// distance (in chunk units) to any agent on the matching `channel` and asks
+/// the map for the desired LOD at that distance.
+///
+/// A change in desired level is applied over two updates rather than
+/// instantly: the first marks the coordinate's [`Lod`] as pending (`current`
+/// unchanged, `desired` set to the new target), and the next settles it
+/// (building the target level and queuing a re-mesh). This gives neighboring
+/// chunks a frame where they can observe a pending transition before the
+/// level actually changes, which is what stitching a high-detail chunk to a
+/// coarser neighbor needs to key off of.
+fn update_lod_from_agents(
+    mut chunkmap_query: Query<&mut Chunkmap>,
+    agent_query: Query<(&ChunkmapAgent, &Transform)>,
 ) {
-    for agent in &agent_query {
-        let chunkmap = commands.entity(agent.chunkmap);
+    for mut chunkmap in &mut chunkmap_query {
+        let width = (Chunk3x16::WIDTH as u32) * chunkmap.chunk_scale.max(1) as u32;
+        let agents: Vec<UVec3> = agent_query
+            .iter()
+            .filter(|(agent, _)| agent.channel == chunkmap.channel)
+            .map(|(_, transform)| (transform.translation.as_uvec3()) / width)
+            .collect();
+        if agents.is_empty() {
+            continue;
+        }
+
+        // `lods` is already keyed by fine coordinate, but `chunks` also holds
+        // coarse chunks whose key's `coord` is an address in that level's own
+        // halved grid (see `ensure_lod`), not a fine coordinate — chaining
+        // those in unfiltered would alias an unrelated fine coordinate that
+        // happens to share the same numeric value. Only level-0 keys are
+        // genuine fine coordinates.
+        let mut coords: HashSet<UVec3> = chunkmap
+            .lods
+            .keys()
+            .copied()
+            .chain(
+                chunkmap
+                    .chunks
+                    .keys()
+                    .filter(|key| key.lod == 0)
+                    .map(|key| key.coord),
+            )
+            .collect();
+        if chunkmap.max_lod > 0 {
+            let extent = max_lod_extent(chunkmap.max_lod);
+            for agent in &agents {
+                coords.extend(chunks_within(*agent, extent));
+            }
+        }
+
+        for coord in coords {
+            let distance = agents
+                .iter()
+                .map(|agent| chebyshev(*agent, coord))
+                .min()
+                .unwrap_or(u32::MAX);
+            let desired = chunkmap.desired_lod(distance);
+            // Copy the pair out instead of holding a borrow of `chunkmap.lods`
+            // into the match arms below, since every arm also needs to mutate
+            // `chunkmap` itself.
+            let existing = chunkmap.lods.get(&coord).map(|lod| (lod.current(), lod.desired()));
+
+            match existing {
+                Some((current, pending)) if current != pending => {
+                    // A transition was marked pending last update; apply it
+                    // now by building (or confirming) the target level and
+                    // queuing the chunk for re-meshing.
+                    let target = pending;
+                    let coarse = coord >> target as u32;
+                    if chunkmap.ensure_lod(coarse, target) {
+                        chunkmap.lods.insert(coord, Lod(target, target));
+                        chunkmap.outdated_chunks.push(ChunkKey {
+                            coord: coarse,
+                            lod: target,
+                        });
+                    }
+                }
+                Some((current, _)) if current != desired => {
+                    // The required level changed again: record the new
+                    // target without touching the level actually in use yet.
+                    chunkmap.lods.insert(coord, Lod(current, desired));
+                }
+                Some(_) => {}
+                None => {
+                    // Never seen before: there is no current level to
+                    // transition away from, so settle at the desired one
+                    // immediately instead of delaying a chunk's first LOD
+                    // assignment by a frame.
+                    //
+                    // `coord` is a full-resolution (level-0) chunk coordinate,
+                    // but a chunk at level `desired` is addressed in its own
+                    // coarser grid where one cell spans `2^desired` fine
+                    // chunks per axis. Convert the fine coordinate to that
+                    // coarse address before building or queuing the chunk, so
+                    // the key lands on the octree node that actually owns
+                    // this region instead of being treated as a coarse
+                    // coordinate directly.
+                    let coarse = coord >> desired as u32;
+                    if chunkmap.ensure_lod(coarse, desired) {
+                        chunkmap.lods.insert(coord, Lod(desired, desired));
+                        chunkmap.outdated_chunks.push(ChunkKey {
+                            coord: coarse,
+                            lod: desired,
+                        });
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// The on-disk path a chunk at `coord` is stored at, relative to a save
+/// directory. One file per chunk keeps streaming reads and writes independent.
+fn chunk_path(dir: &Path, coord: UVec3) -> PathBuf {
+    dir.join(format!("chunk_{}_{}_{}.bin", coord.x, coord.y, coord.z))
+}
+
+/// The chebyshev (chessboard) distance between two chunk coordinates, i.e. the
+/// largest per-axis difference. LOD rings are square, so this is the distance
+/// metric `desired_lod` expects.
+fn chebyshev(a: UVec3, b: UVec3) -> u32 {
+    let d = a.as_ivec3() - b.as_ivec3();
+    d.x.abs().max(d.y.abs()).max(d.z.abs()) as u32
+}
+
+/// Despawns the mesh of a chunk that has left every agent's load radius and
+/// frees its level-0 data, draining the `dropped_chunks` queue that
+/// `mark_chunks_from_agents` fills. Only the exact `ChunkKey { coord, lod: 0
+/// }` entry is removed: a coarser chunk's `coord` lives in that level's own
+/// halved grid rather than fine-coordinate space, so comparing it against a
+/// dropped fine coordinate would risk evicting an unrelated, still-visible
+/// coarse chunk that merely shares the same numeric address.
+fn drop_chunks(mut commands: Commands, mut chunkmap_query: Query<&mut Chunkmap>) {
+    for mut chunkmap in &mut chunkmap_query {
+        while let Some(coord) = chunkmap.dropped_chunks.pop() {
+            if let Some(entity) = chunkmap.chunk_entities.remove(&coord) {
+                commands.entity(entity).despawn_recursive();
+            }
+            chunkmap.chunks.remove(&ChunkKey { coord, lod: 0 });
+            chunkmap.lods.remove(&coord);
+        }
     }
 }
@@ -0,0 +1,547 @@
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    mpsc::{self, Receiver, Sender},
+    Arc, Mutex,
+};
+
+use bevy::{
+    prelude::*,
+    render::{
+        render_resource::{
+            BindGroupEntry, BindGroupLayout, BindGroupLayoutDescriptor, BindGroupLayoutEntry,
+            BindingType, Buffer, BufferBindingType, BufferDescriptor, BufferInitDescriptor,
+            BufferUsages, CachedComputePipelineId, CachedPipelineState, ComputePassDescriptor,
+            ComputePipelineDescriptor, MapMode, PipelineCache, Shader, ShaderStages,
+        },
+        renderer::{RenderDevice, RenderQueue},
+        Render, RenderApp, RenderSet,
+    },
+};
+// `Maintain` is not among `bevy_render`'s `render_resource` re-exports, so it
+// has to come from `wgpu` directly.
+use wgpu::Maintain;
+
+use crate::{
+    chunk::{Chunk, Chunk2x64, Chunk3x16},
+    chunkmap::Chunkmap,
+    planar_terrain::PlanarTerrain,
+};
+
+/// The default 2D WGSL generator. It writes a gentle rolling height field
+/// seeded by the chunk's world offset so the surface stays continuous across
+/// chunk borders. Supply your own [`GenerationShader`] to replace it with real
+/// noise or erosion.
+pub const DEFAULT_GENERATION_WGSL: &str = r#"
+@group(0) @binding(0) var<storage, read_write> heights: array<u32>;
+@group(0) @binding(1) var<uniform> world_offset: vec2<i32>;
+
+const WIDTH: u32 = 64u;
+
+@compute @workgroup_size(8, 8, 1)
+fn generate(@builtin(global_invocation_id) id: vec3<u32>) {
+    if (id.x >= WIDTH || id.y >= WIDTH) {
+        return;
+    }
+    let world = vec2<f32>(
+        f32(i32(id.x) + world_offset.x),
+        f32(i32(id.y) + world_offset.y),
+    );
+    let h = (sin(world.x * 0.1) + cos(world.y * 0.1)) * 0.25 + 0.5;
+    heights[id.x + id.y * WIDTH] = u32(clamp(h, 0.0, 1.0) * 255.0);
+}
+"#;
+
+/// The default 3D WGSL generator. It carves solid voxels below a height
+/// surface derived the same way as the 2D generator, so voxel terrain lines up
+/// with planar terrain and stays continuous across chunk borders.
+pub const DEFAULT_VOXEL_WGSL: &str = r#"
+@group(0) @binding(0) var<storage, read_write> voxels: array<u32>;
+@group(0) @binding(1) var<uniform> world_offset: vec4<i32>;
+
+const WIDTH: u32 = 16u;
+
+@compute @workgroup_size(4, 4, 4)
+fn generate(@builtin(global_invocation_id) id: vec3<u32>) {
+    if (id.x >= WIDTH || id.y >= WIDTH || id.z >= WIDTH) {
+        return;
+    }
+    let wx = f32(i32(id.x) + world_offset.x);
+    let wz = f32(i32(id.z) + world_offset.z);
+    let surface = (sin(wx * 0.1) + cos(wz * 0.1)) * 2.0 + 8.0;
+    let wy = f32(i32(id.y) + world_offset.y);
+    var material: u32 = 0u;
+    if (wy < surface) {
+        material = 1u;
+    }
+    voxels[id.x + id.y * WIDTH + id.z * WIDTH * WIDTH] = material;
+}
+"#;
+
+/// Supplies the WGSL compute shaders that fill chunk data on the GPU.
+/// Implement this to plug in your own generator; the 2D shader must define a
+/// `generate` entry point that writes `WIDTH * WIDTH` height values into the
+/// `read_write` storage buffer at group `0` binding `0`, reading the chunk's
+/// world offset from the uniform at group `0` binding `1` so values stay
+/// continuous across chunk borders. The 3D shader is the same, writing
+/// `WIDTH * WIDTH * WIDTH` voxels.
+pub trait GenerationShader: Send + Sync + 'static {
+    /// The WGSL source of the 2D heightmap compute shader.
+    fn source(&self) -> &str;
+
+    /// The WGSL source of the 3D voxel compute shader. Defaults to
+    /// [`DEFAULT_VOXEL_WGSL`].
+    fn voxel_source(&self) -> &str {
+        DEFAULT_VOXEL_WGSL
+    }
+}
+
+/// The built-in generator backed by [`DEFAULT_GENERATION_WGSL`] and
+/// [`DEFAULT_VOXEL_WGSL`].
+pub struct DefaultGenerator;
+
+impl GenerationShader for DefaultGenerator {
+    fn source(&self) -> &str {
+        DEFAULT_GENERATION_WGSL
+    }
+}
+
+/// Which mesher a generation request feeds: the 2D `PlanarTerrain` heightmapper
+/// or the 3D `Chunkmap` voxel mesher. Picks the pipeline and buffer size.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum GenerationTarget {
+    Planar,
+    Voxel,
+}
+
+/// A request to synthesise one chunk on the GPU, sent from the main world to
+/// the render world. `coord` is the chunk coordinate; its world offset is
+/// derived in the render world so noise is continuous across borders.
+pub struct GenerationRequest {
+    pub target: GenerationTarget,
+    pub coord: IVec3,
+}
+
+/// A finished chunk read back from the GPU, sent from the render world to the
+/// main world to be written into its chunk.
+pub struct GenerationResult {
+    pub target: GenerationTarget,
+    pub coord: IVec3,
+    pub data: Vec<u8>,
+}
+
+/// The cross-world plumbing between the main-world systems that request and
+/// apply generation and the render-world systems that dispatch it. Cloned into
+/// both the main app and the render sub-app so each end holds its channel
+/// halves; `Receiver`s are wrapped so the resource stays `Sync`.
+#[derive(Resource, Clone)]
+struct GenerationIo {
+    request_tx: Sender<GenerationRequest>,
+    request_rx: Arc<Mutex<Receiver<GenerationRequest>>>,
+    result_tx: Sender<GenerationResult>,
+    result_rx: Arc<Mutex<Receiver<GenerationResult>>>,
+}
+
+/// Wires up GPU terrain generation. This plugin is what gives
+/// [`TerrainGenerator`] a call site: it builds the generator from the render
+/// app's `PipelineCache` (the only world that owns one) and inserts it there,
+/// runs the dispatch/readback systems in the render world, and runs the
+/// request/apply systems in the main world, bridging the two over channels.
+pub struct TerrainGeneratorPlugin {
+    /// The shader sources used to build the compute pipelines.
+    pub shader: Arc<dyn GenerationShader>,
+}
+
+impl Default for TerrainGeneratorPlugin {
+    fn default() -> Self {
+        Self {
+            shader: Arc::new(DefaultGenerator),
+        }
+    }
+}
+
+impl Plugin for TerrainGeneratorPlugin {
+    fn build(&self, app: &mut App) {
+        let (request_tx, request_rx) = mpsc::channel();
+        let (result_tx, result_rx) = mpsc::channel();
+        let io = GenerationIo {
+            request_tx,
+            request_rx: Arc::new(Mutex::new(request_rx)),
+            result_tx,
+            result_rx: Arc::new(Mutex::new(result_rx)),
+        };
+
+        app.insert_resource(io.clone());
+        app.add_systems(Update, (queue_generation_requests, apply_generation_results));
+
+        if let Ok(render_app) = app.get_sub_app_mut(RenderApp) {
+            render_app.insert_resource(io);
+            render_app.add_systems(
+                Render,
+                (dispatch_generation, receive_generation)
+                    .chain()
+                    .in_set(RenderSet::Queue),
+            );
+        }
+    }
+
+    fn finish(&self, app: &mut App) {
+        // Compile the shader sources into assets in the main world, then hand
+        // the handles to the render world, where the pipelines are built.
+        let (planar_shader, voxel_shader) = {
+            let mut shaders = app.world.resource_mut::<Assets<Shader>>();
+            (
+                shaders.add(Shader::from_wgsl(
+                    self.shader.source().to_string(),
+                    "bevy_terrain/planar_generate.wgsl",
+                )),
+                shaders.add(Shader::from_wgsl(
+                    self.shader.voxel_source().to_string(),
+                    "bevy_terrain/voxel_generate.wgsl",
+                )),
+            )
+        };
+
+        let Ok(render_app) = app.get_sub_app_mut(RenderApp) else {
+            return;
+        };
+        let device = render_app.world.resource::<RenderDevice>().clone();
+        let queue = render_app.world.resource::<RenderQueue>().clone();
+        let generator = {
+            let cache = render_app.world.resource::<PipelineCache>();
+            TerrainGenerator::new(device, queue, cache, planar_shader, voxel_shader)
+        };
+        render_app.insert_resource(generator);
+    }
+}
+
+/// A compute dispatch whose readback is still in flight. `ready` is flipped by
+/// the `map_async` callback once the GPU has finished and the staging buffer is
+/// mapped, so the poll loop never blocks waiting on the device.
+struct InFlight {
+    target: GenerationTarget,
+    coord: IVec3,
+    readback: Buffer,
+    ready: Arc<AtomicBool>,
+}
+
+/// Offloads chunk synthesis onto compute shaders, dispatching this frame and
+/// completing the readback over following frames without ever blocking the
+/// calling thread. Lives in the render world, where `RenderDevice`,
+/// `RenderQueue`, and `PipelineCache` are available.
+#[derive(Resource)]
+pub struct TerrainGenerator {
+    device: RenderDevice,
+    queue: RenderQueue,
+    planar_pipeline: CachedComputePipelineId,
+    voxel_pipeline: CachedComputePipelineId,
+    planar_layout: BindGroupLayout,
+    voxel_layout: BindGroupLayout,
+    /// Requests whose pipeline was still compiling when they arrived; retried
+    /// every frame until the pipeline is ready.
+    pending: Vec<GenerationRequest>,
+    /// Dispatches whose readback has not completed yet.
+    in_flight: Vec<InFlight>,
+}
+
+impl TerrainGenerator {
+    /// Builds a generator, queuing both compute pipelines through the render
+    /// app's [`PipelineCache`]. The pipelines compile lazily, so requests that
+    /// arrive before a pipeline is ready are retried on later frames rather
+    /// than dropped.
+    fn new(
+        device: RenderDevice,
+        queue: RenderQueue,
+        pipeline_cache: &PipelineCache,
+        planar_shader: Handle<Shader>,
+        voxel_shader: Handle<Shader>,
+    ) -> Self {
+        let planar_layout = storage_and_uniform_layout(&device, "terrain_generator_planar_layout");
+        let voxel_layout = storage_and_uniform_layout(&device, "terrain_generator_voxel_layout");
+
+        let planar_pipeline = pipeline_cache.queue_compute_pipeline(ComputePipelineDescriptor {
+            label: Some("terrain_generator_planar_pipeline".into()),
+            layout: vec![planar_layout.clone()],
+            push_constant_ranges: vec![],
+            shader: planar_shader,
+            shader_defs: vec![],
+            entry_point: "generate".into(),
+        });
+        let voxel_pipeline = pipeline_cache.queue_compute_pipeline(ComputePipelineDescriptor {
+            label: Some("terrain_generator_voxel_pipeline".into()),
+            layout: vec![voxel_layout.clone()],
+            push_constant_ranges: vec![],
+            shader: voxel_shader,
+            shader_defs: vec![],
+            entry_point: "generate".into(),
+        });
+
+        Self {
+            device,
+            queue,
+            planar_pipeline,
+            voxel_pipeline,
+            planar_layout,
+            voxel_layout,
+            pending: vec![],
+            in_flight: vec![],
+        }
+    }
+
+    /// Dispatches every request whose pipeline is ready, re-queuing the rest.
+    /// Each dispatch submits its compute pass and registers an asynchronous
+    /// readback; the result is collected later by [`poll`](Self::poll).
+    fn dispatch(&mut self, pipeline_cache: &PipelineCache, requests: Vec<GenerationRequest>) {
+        let queued: Vec<GenerationRequest> = self.pending.drain(..).chain(requests).collect();
+        for request in queued {
+            if !self.try_dispatch(pipeline_cache, &request) {
+                self.pending.push(request);
+            }
+        }
+    }
+
+    /// Attempts a single dispatch, returning `false` (so the caller can retry)
+    /// if the relevant pipeline has not finished compiling yet.
+    fn try_dispatch(&mut self, pipeline_cache: &PipelineCache, request: &GenerationRequest) -> bool {
+        let (id, layout, cells, width, uniform) = match request.target {
+            GenerationTarget::Planar => {
+                let offset = request.coord * (Chunk2x64::WIDTH as i32);
+                (
+                    self.planar_pipeline,
+                    &self.planar_layout,
+                    Chunk2x64::SIZE,
+                    Chunk2x64::WIDTH,
+                    vec![offset.x, offset.y],
+                )
+            }
+            GenerationTarget::Voxel => {
+                let offset = request.coord * (Chunk3x16::WIDTH as i32);
+                (
+                    self.voxel_pipeline,
+                    &self.voxel_layout,
+                    Chunk3x16::SIZE,
+                    Chunk3x16::WIDTH,
+                    vec![offset.x, offset.y, offset.z, 0],
+                )
+            }
+        };
+
+        if !matches!(pipeline_cache.get_compute_pipeline_state(id), CachedPipelineState::Ok(_)) {
+            return false;
+        }
+        let Some(pipeline) = pipeline_cache.get_compute_pipeline(id) else {
+            return false;
+        };
+
+        let byte_len = (cells * std::mem::size_of::<u32>()) as u64;
+        let storage = self.device.create_buffer(&BufferDescriptor {
+            label: Some("terrain_generator_storage"),
+            size: byte_len,
+            usage: BufferUsages::STORAGE | BufferUsages::COPY_SRC,
+            mapped_at_creation: false,
+        });
+        let readback = self.device.create_buffer(&BufferDescriptor {
+            label: Some("terrain_generator_readback"),
+            size: byte_len,
+            usage: BufferUsages::COPY_DST | BufferUsages::MAP_READ,
+            mapped_at_creation: false,
+        });
+        let uniform_buffer = self.device.create_buffer_with_data(&BufferInitDescriptor {
+            label: Some("terrain_generator_offset"),
+            contents: bytemuck::cast_slice(&uniform),
+            usage: BufferUsages::UNIFORM,
+        });
+
+        let bind_group = self.device.create_bind_group(
+            Some("terrain_generator_bind_group"),
+            layout,
+            &[
+                BindGroupEntry {
+                    binding: 0,
+                    resource: storage.as_entire_binding(),
+                },
+                BindGroupEntry {
+                    binding: 1,
+                    resource: uniform_buffer.as_entire_binding(),
+                },
+            ],
+        );
+
+        let mut encoder = self.device.create_command_encoder(&default());
+        {
+            let mut pass = encoder.begin_compute_pass(&ComputePassDescriptor {
+                label: Some("terrain_generator_pass"),
+            });
+            pass.set_pipeline(pipeline);
+            pass.set_bind_group(0, &bind_group, &[]);
+            // One workgroup covers an 8×8 (2D) or 4×4×4 (3D) block; WIDTH is a
+            // power of two, so this tiles the chunk exactly. The third
+            // dimension is 1 for the planar pipeline.
+            let (gx, gy, gz) = match request.target {
+                GenerationTarget::Planar => ((width / 8) as u32, (width / 8) as u32, 1),
+                GenerationTarget::Voxel => {
+                    ((width / 4) as u32, (width / 4) as u32, (width / 4) as u32)
+                }
+            };
+            pass.dispatch_workgroups(gx, gy, gz);
+        }
+        encoder.copy_buffer_to_buffer(&storage, 0, &readback, 0, byte_len);
+        self.queue.submit([encoder.finish()]);
+
+        // Register an asynchronous map; the callback flips `ready` once the GPU
+        // has finished, which `poll` observes without ever blocking.
+        let ready = Arc::new(AtomicBool::new(false));
+        let flag = ready.clone();
+        readback.slice(..).map_async(MapMode::Read, move |result| {
+            if result.is_ok() {
+                flag.store(true, Ordering::Release);
+            }
+        });
+
+        self.in_flight.push(InFlight {
+            target: request.target,
+            coord: request.coord,
+            readback,
+            ready,
+        });
+        true
+    }
+
+    /// Advances in-flight readbacks without blocking and collects any that have
+    /// completed. `Maintain::Poll` services mapping callbacks that are ready
+    /// and returns immediately, so a dispatch started this frame is picked up
+    /// on a following frame rather than stalling the render thread.
+    fn poll(&mut self) -> Vec<GenerationResult> {
+        if self.in_flight.is_empty() {
+            return vec![];
+        }
+        self.device.poll(Maintain::Poll);
+
+        let mut finished = vec![];
+        let mut index = 0;
+        while index < self.in_flight.len() {
+            if self.in_flight[index].ready.load(Ordering::Acquire) {
+                let flight = self.in_flight.remove(index);
+                let data = {
+                    let view = flight.readback.slice(..).get_mapped_range();
+                    view.chunks_exact(4)
+                        .map(|raw| u32::from_ne_bytes(raw.try_into().unwrap()) as u8)
+                        .collect()
+                };
+                flight.readback.unmap();
+                finished.push(GenerationResult {
+                    target: flight.target,
+                    coord: flight.coord,
+                    data,
+                });
+            } else {
+                index += 1;
+            }
+        }
+        finished
+    }
+}
+
+/// The bind group layout shared by both pipelines: a read-write storage buffer
+/// for the output and a uniform for the chunk's world offset.
+fn storage_and_uniform_layout(device: &RenderDevice, label: &str) -> BindGroupLayout {
+    device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+        label: Some(label),
+        entries: &[
+            BindGroupLayoutEntry {
+                binding: 0,
+                visibility: ShaderStages::COMPUTE,
+                ty: BindingType::Buffer {
+                    ty: BufferBindingType::Storage { read_only: false },
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            },
+            BindGroupLayoutEntry {
+                binding: 1,
+                visibility: ShaderStages::COMPUTE,
+                ty: BindingType::Buffer {
+                    ty: BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            },
+        ],
+    })
+}
+
+/// Main-world system: drains each terrain's pending-generation queue and sends
+/// a request to the render world for every freshly allocated chunk.
+fn queue_generation_requests(
+    io: Res<GenerationIo>,
+    mut planar_query: Query<&mut PlanarTerrain>,
+    mut voxel_query: Query<&mut Chunkmap>,
+) {
+    for mut terrain in &mut planar_query {
+        for coord in terrain.take_to_generate() {
+            let _ = io.request_tx.send(GenerationRequest {
+                target: GenerationTarget::Planar,
+                coord: coord.as_ivec2().extend(0),
+            });
+        }
+    }
+    for mut map in &mut voxel_query {
+        for coord in map.take_to_generate() {
+            let _ = io.request_tx.send(GenerationRequest {
+                target: GenerationTarget::Voxel,
+                coord: coord.as_ivec3(),
+            });
+        }
+    }
+}
+
+/// Main-world system: applies finished generation results back into their
+/// terrains, which re-queues the affected chunks for meshing.
+fn apply_generation_results(
+    io: Res<GenerationIo>,
+    mut planar_query: Query<&mut PlanarTerrain>,
+    mut voxel_query: Query<&mut Chunkmap>,
+) {
+    let results: Vec<GenerationResult> = {
+        let rx = io.result_rx.lock().unwrap();
+        rx.try_iter().collect()
+    };
+    for result in results {
+        match result.target {
+            GenerationTarget::Planar => {
+                let coord = result.coord.truncate().as_uvec2();
+                for mut terrain in &mut planar_query {
+                    terrain.apply_generated(coord, &result.data);
+                }
+            }
+            GenerationTarget::Voxel => {
+                let coord = result.coord.as_uvec3();
+                for mut map in &mut voxel_query {
+                    map.apply_generated(coord, &result.data);
+                }
+            }
+        }
+    }
+}
+
+/// Render-world system: forwards queued requests to the generator for dispatch.
+fn dispatch_generation(
+    mut generator: ResMut<TerrainGenerator>,
+    pipeline_cache: Res<PipelineCache>,
+    io: Res<GenerationIo>,
+) {
+    let requests: Vec<GenerationRequest> = {
+        let rx = io.request_rx.lock().unwrap();
+        rx.try_iter().collect()
+    };
+    generator.dispatch(&pipeline_cache, requests);
+}
+
+/// Render-world system: collects completed readbacks and ships them back to the
+/// main world.
+fn receive_generation(mut generator: ResMut<TerrainGenerator>, io: Res<GenerationIo>) {
+    for result in generator.poll() {
+        let _ = io.result_tx.send(result);
+    }
+}
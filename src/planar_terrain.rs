@@ -1,16 +1,25 @@
+use std::path::{Path, PathBuf};
+
 use bevy::{
     prelude::*,
     render::{mesh::Indices, render_resource::PrimitiveTopology},
     utils::HashMap,
 };
-use ndshape::Shape;
 
-use crate::chunk::Chunk2x64;
+use crate::chunk::{Chunk, Chunk2x64, Sampleable};
 
 #[derive(Component)]
 pub struct PlanarTerrain {
     chunks: HashMap<UVec2, Chunk2x64>,
     outdated_chunks: Vec<UVec2>,
+    /// Freshly allocated chunks awaiting GPU generation, collected so the
+    /// `TerrainGenerator` can dispatch them asynchronously. A chunk stays flat
+    /// until its generated heights are applied and it is re-queued for meshing.
+    to_generate: Vec<UVec2>,
+    /// Directory chunks are persisted to, keyed by coordinate. When set,
+    /// entering a region reads a saved chunk from disk instead of allocating a
+    /// fresh one. `None` keeps the terrain entirely in memory.
+    save_dir: Option<PathBuf>,
 }
 
 impl PlanarTerrain {
@@ -18,6 +27,88 @@ impl PlanarTerrain {
         Self {
             chunks: HashMap::new(),
             outdated_chunks: vec![UVec2 { x: 0, y: 0 }],
+            to_generate: vec![],
+            save_dir: None,
+        }
+    }
+
+    /// Backs this terrain with a directory on disk. Chunks written by
+    /// [`save_chunks`](Self::save_chunks) land here, and chunks entering the
+    /// view are reloaded from here when a matching file exists.
+    pub fn with_save_dir(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.save_dir = Some(dir.into());
+        self
+    }
+
+    /// Writes every loaded chunk to its own file under the save directory,
+    /// keyed by chunk coordinate. Does nothing when no save directory is set.
+    pub fn save_chunks(&self) -> std::io::Result<()> {
+        let Some(dir) = &self.save_dir else {
+            return Ok(());
+        };
+        std::fs::create_dir_all(dir)?;
+        for (coord, chunk) in &self.chunks {
+            std::fs::write(chunk_path(dir, *coord), chunk.serialize())?;
+        }
+        Ok(())
+    }
+
+    /// Loads the chunk at `coord` from the save directory, falling back to a
+    /// fresh empty chunk when no save directory is set or no file exists yet.
+    /// The returned `bool` reports whether a file was actually found, so the
+    /// caller can tell a chunk that genuinely needs GPU generation apart from
+    /// one that was just reloaded from disk.
+    fn load_or_create(&self, coord: UVec2) -> (Chunk2x64, bool) {
+        match self
+            .save_dir
+            .as_ref()
+            .and_then(|dir| std::fs::read(chunk_path(dir, coord)).ok())
+        {
+            Some(bytes) => (Chunk2x64::deserialize(&bytes), true),
+            None => (Chunk2x64::new(), false),
+        }
+    }
+
+    /// Drains the chunk coordinates awaiting GPU generation so the terrain
+    /// generator can dispatch them. Returns an empty vector when nothing is
+    /// pending or no generator is wired up.
+    pub(crate) fn take_to_generate(&mut self) -> Vec<UVec2> {
+        std::mem::take(&mut self.to_generate)
+    }
+
+    /// Writes a generated height array back into the chunk at `coord` and
+    /// re-queues it for meshing. Ignored when the chunk is no longer loaded
+    /// (e.g. it was streamed out before generation finished).
+    pub(crate) fn apply_generated(&mut self, coord: UVec2, heights: &[u8]) {
+        let Some(chunk) = self.chunks.get_mut(&coord) else {
+            return;
+        };
+        for index in 0..Chunk2x64::SIZE {
+            chunk.write(Chunk2x64::delinearize(index), heights[index]);
+        }
+        self.outdated_chunks.push(coord);
+    }
+
+    /// Samples the terrain at a global tile coordinate, transparently crossing
+    /// chunk boundaries. The coordinate is floor-divided by `Chunk::WIDTH` to
+    /// find which chunk owns the tile and where inside that chunk the tile
+    /// lives; if the owning chunk is not currently loaded (or lies in the
+    /// negative, unkeyed quadrant), an "ambient" default value is returned so
+    /// callers can safely read one tile past a chunk edge. This is what lets
+    /// meshing compute seamless normals across borders, instead of treating
+    /// each chunk in complete isolation.
+    pub fn sample_world(&self, pos: IVec2) -> u8 {
+        let width = Chunk2x64::WIDTH as i32;
+        let chunk_coord = IVec2::new(pos.x.div_euclid(width), pos.y.div_euclid(width));
+        if chunk_coord.x < 0 || chunk_coord.y < 0 {
+            return u8::default();
+        }
+        match self.chunks.get(&chunk_coord.as_uvec2()) {
+            Some(chunk) => chunk.sample([
+                pos.x.rem_euclid(width) as usize,
+                pos.y.rem_euclid(width) as usize,
+            ]),
+            None => u8::default(),
         }
     }
 }
@@ -30,6 +121,12 @@ impl Plugin for PlanarTerrainMeshingPlugin {
     }
 }
 
+/// The on-disk path a chunk at `coord` is stored at, relative to a save
+/// directory. One file per chunk keeps streaming reads and writes independent.
+fn chunk_path(dir: &Path, coord: UVec2) -> PathBuf {
+    dir.join(format!("chunk_{}_{}.bin", coord.x, coord.y))
+}
+
 fn outdated_chunks() {}
 
 fn planar_meshing(
@@ -42,7 +139,13 @@ fn planar_meshing(
         while !terrain.outdated_chunks.is_empty() {
             let coords = terrain.outdated_chunks.pop().unwrap();
             if !terrain.chunks.contains_key(&coords) {
-                terrain.chunks.insert(coords, Chunk2::new());
+                let (chunk, from_disk) = terrain.load_or_create(coords);
+                terrain.chunks.insert(coords, chunk);
+                // A freshly allocated (not loaded) chunk starts flat and is
+                // queued for asynchronous GPU generation.
+                if !from_disk {
+                    terrain.to_generate.push(coords);
+                }
             }
             let chunk = terrain.chunks.get(&coords).unwrap();
 
@@ -50,32 +153,61 @@ fn planar_meshing(
             let mut normals = vec![];
             let mut texture_coordinates = vec![];
 
+            // Spacing between two adjacent vertices in chunk-local space. The
+            // `2.0 * cell_spacing` term in the normal is the world-space run
+            // between the two neighbors we take the central difference over.
+            let cell_spacing = 1.0 / (Chunk2x64::WIDTH as f32);
+
+            // Global tile coordinate of this chunk's origin, so neighbor samples
+            // one tile past an edge resolve into the adjacent chunk instead of
+            // clamping to the border.
+            let base = coords.as_ivec2() * (Chunk2x64::WIDTH as i32);
+
             for (pos, val) in chunk.iter() {
                 vertices.push(Vec3 {
-                    x: (pos[0] as f32) / (Chunk2::DIM as f32),
+                    x: (pos[0] as f32) / (Chunk2x64::WIDTH as f32),
                     y: (val as f32) / (u8::MAX as f32),
-                    z: (pos[1] as f32) / (Chunk2::DIM as f32),
-                });
-                normals.push(Vec3 {
-                    x: 0.0,
-                    y: 1.0,
-                    z: 0.0,
+                    z: (pos[1] as f32) / (Chunk2x64::WIDTH as f32),
                 });
+
+                // Derive a smooth per-vertex normal from the height field using
+                // central differences of the four neighbors. Neighbors are read
+                // through `sample_world` so the difference crosses chunk borders
+                // into adjacent chunks, eliminating seams and lighting
+                // discontinuities; a missing neighbor falls back to the ambient
+                // default.
+                let world = base + IVec2::new(pos[0] as i32, pos[1] as i32);
+                let height_at = |dx: i32, dy: i32| {
+                    terrain.sample_world(world + IVec2::new(dx, dy)) as f32 / (u8::MAX as f32)
+                };
+                let h_l = height_at(-1, 0);
+                let h_r = height_at(1, 0);
+                let h_d = height_at(0, -1);
+                let h_u = height_at(0, 1);
+                normals.push(
+                    Vec3 {
+                        x: h_l - h_r,
+                        y: 2.0 * cell_spacing,
+                        z: h_d - h_u,
+                    }
+                    .normalize(),
+                );
+
                 texture_coordinates.push(Vec2 {
-                    x: (pos[0] as f32) / (Chunk2::DIM as f32),
-                    y: (pos[1] as f32) / (Chunk2::DIM as f32),
+                    x: (pos[0] as f32) / (Chunk2x64::WIDTH as f32),
+                    y: (pos[1] as f32) / (Chunk2x64::WIDTH as f32),
                 });
             }
 
             let mut indices = vec![];
-            for x in 0..Chunk2::DIM - 1 {
-                for y in 0..Chunk2::DIM - 1 {
-                    indices.push(Chunk2::SHAPE.linearize([x, y]) as u32);
-                    indices.push(Chunk2::SHAPE.linearize([x, y + 1]) as u32);
-                    indices.push(Chunk2::SHAPE.linearize([x + 1, y]) as u32);
-                    indices.push(Chunk2::SHAPE.linearize([x + 1, y]) as u32);
-                    indices.push(Chunk2::SHAPE.linearize([x, y + 1]) as u32);
-                    indices.push(Chunk2::SHAPE.linearize([x + 1, y + 1]) as u32);
+            for x in 0..Chunk2x64::WIDTH - 1 {
+                for y in 0..Chunk2x64::WIDTH - 1 {
+                    indices.push(Chunk2x64::linearize([x, y]) as u32);
+                    indices.push(Chunk2x64::linearize([x, y + 1]) as u32);
+                    indices.push(Chunk2x64::linearize([x + 1, y]) as u32);
+                    indices.push(Chunk2x64::linearize([x + 1, y]) as u32);
+                    indices.push(Chunk2x64::linearize([x, y + 1]) as u32);
+                    indices.push(Chunk2x64::linearize([x + 1, y + 1]) as u32);
                 }
             }
 
@@ -0,0 +1,231 @@
+use bevy::{
+    prelude::*,
+    render::{mesh::Indices, render_resource::PrimitiveTopology},
+};
+
+use crate::{
+    chunk::{Chunk, Chunk3x16, Sampleable},
+    chunkmap::Chunkmap,
+};
+
+/// Meshes the 3D voxel chunks held by a [`Chunkmap`]. Where `PlanarTerrain`
+/// turns a 2D height field into a surface, this plugin turns a 3D voxel grid
+/// into geometry, emitting only the faces that are actually exposed and
+/// merging coplanar same-material faces into as few quads as possible.
+pub struct VoxelTerrainMeshingPlugin;
+
+impl Plugin for VoxelTerrainMeshingPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Update, voxel_meshing);
+    }
+}
+
+fn voxel_meshing(
+    mut commands: Commands,
+    mut query: Query<&mut Chunkmap>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+) {
+    for mut map in query.iter_mut() {
+        for key in map.take_outdated() {
+            let Some(chunk) = map.chunk(key) else {
+                continue;
+            };
+
+            // A chunk at level `lod` is addressed in a coarser grid whose cells
+            // span `2^lod` fine chunks per axis, and each of its voxels stands
+            // in for a `2^lod` block of fine voxels. Scale the chunk's origin
+            // and footprint by that factor so a coarse chunk covers the same
+            // world region as the fine chunks it replaces instead of collapsing
+            // into a single fine chunk's worth of space.
+            let stride = 1i32 << key.lod;
+            let base = key.coord.as_ivec3() * (Chunk3x16::WIDTH as i32) * stride;
+            let mesh = greedy_mesh(chunk, base, stride, key.lod, &map);
+
+            let entity = commands
+                .spawn(PbrBundle {
+                    mesh: meshes.add(mesh),
+                    material: materials.add(Color::rgb(0.5, 0.5, 0.5).into()),
+                    transform: Transform::from_translation(base.as_vec3())
+                        .with_scale(Vec3::splat(stride as f32)),
+                    ..default()
+                })
+                .id();
+
+            // Replace any mesh left over from the previous detail level so a
+            // re-mesh never stacks two surfaces on top of each other.
+            if let Some(previous) = map.replace_chunk_entity(key.coord, entity) {
+                commands.entity(previous).despawn_recursive();
+            }
+        }
+    }
+}
+
+/// Greedily meshes a single voxel chunk. A voxel is solid when its `DataType`
+/// is nonzero, and only faces between a solid voxel and an empty one are
+/// emitted. The chunk's six boundary planes read their far neighbor through
+/// [`Chunkmap::sample_world`] so faces are culled correctly against adjacent
+/// chunks instead of always appearing at the border. `stride` is the number of
+/// fine voxels a single voxel of this chunk stands for (`2^lod`), used to step
+/// boundary-plane neighbor samples out by one coarse voxel in full-resolution
+/// coordinates; `lod` is passed through to `sample_world` so that step always
+/// lands on a neighbor at the same level of detail as this chunk instead of
+/// an unrelated level-0 chunk.
+///
+/// For each axis `d` and each of its two face directions the chunk is swept
+/// slice by slice. Each slice is reduced to a `WIDTH × WIDTH` mask tagged with
+/// the material of any exposed face, and the mask is then consumed greedily:
+/// each run is grown as far as it can along `u`, then along `v`, emitted as one
+/// quad, and zeroed out so it is not visited again.
+fn greedy_mesh(chunk: &Chunk3x16, base: IVec3, stride: i32, lod: u8, map: &Chunkmap) -> Mesh {
+    let w = Chunk3x16::WIDTH as i32;
+
+    // The material at a chunk-local voxel, reading neighbor chunks for any
+    // coordinate that falls outside this one. Zero means empty. Out-of-range
+    // samples are scaled by `stride` so a coarse chunk reads its neighbor one
+    // coarse voxel out in full-resolution coordinates rather than one fine
+    // voxel out, and sampled at this chunk's own `lod` so the neighbor is read
+    // from the matching level of detail.
+    let voxel = |local: IVec3| -> u8 {
+        if (0..w).contains(&local.x) && (0..w).contains(&local.y) && (0..w).contains(&local.z) {
+            chunk.sample([local.x as usize, local.y as usize, local.z as usize])
+        } else {
+            map.sample_world(base + local * stride, lod)
+        }
+    };
+
+    let mut positions: Vec<Vec3> = vec![];
+    let mut normals: Vec<Vec3> = vec![];
+    let mut uvs: Vec<Vec2> = vec![];
+    let mut indices: Vec<u32> = vec![];
+
+    for d in 0..3usize {
+        let u = (d + 1) % 3;
+        let v = (d + 2) % 3;
+
+        for dir in [1i32, -1i32] {
+            let mut offset = [0i32; 3];
+            offset[d] = dir;
+            let offset = IVec3::from_array(offset);
+
+            let mut normal = [0.0f32; 3];
+            normal[d] = dir as f32;
+            let normal = Vec3::from_array(normal);
+
+            for slice in 0..w {
+                // The exposed face of a `+dir` slice sits on its far side.
+                let face_d = slice + if dir > 0 { 1 } else { 0 };
+
+                // Build the mask: a cell holds the material of an exposed face,
+                // or zero where the near voxel is empty or its far neighbor is
+                // solid (and so the face is hidden).
+                let mut mask = vec![0u8; (w * w) as usize];
+                for j in 0..w {
+                    for i in 0..w {
+                        let mut near = [0i32; 3];
+                        near[d] = slice;
+                        near[u] = i;
+                        near[v] = j;
+                        let near = IVec3::from_array(near);
+
+                        let material = voxel(near);
+                        if material != 0 && voxel(near + offset) == 0 {
+                            mask[(i + j * w) as usize] = material;
+                        }
+                    }
+                }
+
+                // Consume the mask greedily.
+                for j in 0..w {
+                    let mut i = 0;
+                    while i < w {
+                        let material = mask[(i + j * w) as usize];
+                        if material == 0 {
+                            i += 1;
+                            continue;
+                        }
+
+                        // Grow the quad along `u` while the material matches.
+                        let mut quad_w = 1;
+                        while i + quad_w < w
+                            && mask[(i + quad_w + j * w) as usize] == material
+                        {
+                            quad_w += 1;
+                        }
+
+                        // Grow the quad along `v`, one full row at a time.
+                        let mut quad_h = 1;
+                        'height: while j + quad_h < w {
+                            for k in 0..quad_w {
+                                if mask[(i + k + (j + quad_h) * w) as usize] != material {
+                                    break 'height;
+                                }
+                            }
+                            quad_h += 1;
+                        }
+
+                        let corner = |ui: i32, vj: i32| {
+                            let mut c = [0.0f32; 3];
+                            c[d] = face_d as f32;
+                            c[u] = ui as f32;
+                            c[v] = vj as f32;
+                            Vec3::from_array(c)
+                        };
+
+                        let start = positions.len() as u32;
+                        positions.push(corner(i, j));
+                        positions.push(corner(i + quad_w, j));
+                        positions.push(corner(i + quad_w, j + quad_h));
+                        positions.push(corner(i, j + quad_h));
+                        for _ in 0..4 {
+                            normals.push(normal);
+                        }
+                        uvs.push(Vec2::new(0.0, 0.0));
+                        uvs.push(Vec2::new(quad_w as f32, 0.0));
+                        uvs.push(Vec2::new(quad_w as f32, quad_h as f32));
+                        uvs.push(Vec2::new(0.0, quad_h as f32));
+
+                        // Wind the two triangles so the face points along
+                        // `dir`; `u × v == +d`, so a positive face keeps the
+                        // natural order and a negative face reverses it.
+                        if dir > 0 {
+                            indices.extend_from_slice(&[
+                                start,
+                                start + 1,
+                                start + 2,
+                                start,
+                                start + 2,
+                                start + 3,
+                            ]);
+                        } else {
+                            indices.extend_from_slice(&[
+                                start,
+                                start + 2,
+                                start + 1,
+                                start,
+                                start + 3,
+                                start + 2,
+                            ]);
+                        }
+
+                        // Zero out every mask cell this quad covered.
+                        for dj in 0..quad_h {
+                            for di in 0..quad_w {
+                                mask[(i + di + (j + dj) * w) as usize] = 0;
+                            }
+                        }
+
+                        i += quad_w;
+                    }
+                }
+            }
+        }
+    }
+
+    let mut mesh = Mesh::new(PrimitiveTopology::TriangleList);
+    mesh.insert_attribute(Mesh::ATTRIBUTE_POSITION, positions);
+    mesh.insert_attribute(Mesh::ATTRIBUTE_NORMAL, normals);
+    mesh.insert_attribute(Mesh::ATTRIBUTE_UV_0, uvs);
+    mesh.set_indices(Some(Indices::U32(indices)));
+    mesh
+}